@@ -0,0 +1,637 @@
+//! Scryfall-backed caching and query engine behind `foretell`'s card picker.
+//!
+//! [`CardCache`] owns the on-disk set/card list cache and a content-addressed
+//! image cache, and knows how to refresh both in the background. Front ends
+//! plug in their own progress/error reporting via [`Notifier`] instead of
+//! this crate hard-coding a particular notification mechanism, so the same
+//! engine can back a dmenu+sxiv binary, a TUI, or anything else.
+
+use anyhow::Context;
+use chrono::{Duration, NaiveDate};
+use futures_util::{stream::StreamExt, TryStreamExt};
+use scryfall::{
+    card::{Card, Game},
+    error::ScryfallError,
+    set::{SetCode, SetType},
+    Error, Set,
+};
+use std::{
+    collections::HashSet,
+    future,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread::available_parallelism,
+    time::SystemTime,
+};
+use tempfile::NamedTempFile;
+use tokio::{
+    fs::{self, File, OpenOptions},
+    io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter},
+    sync::Mutex,
+    task::JoinHandle,
+};
+
+/// Default budget for the on-disk image cache; the oldest entries are
+/// evicted once the cache grows past this size. Overridable by setting
+/// `FORETELL_IMAGE_CACHE_BYTES`.
+const DEFAULT_IMAGE_CACHE_BUDGET_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Reads the configured image cache budget, falling back to
+/// [`DEFAULT_IMAGE_CACHE_BUDGET_BYTES`] if `FORETELL_IMAGE_CACHE_BYTES` is
+/// unset or isn't a valid byte count.
+fn image_cache_budget_bytes() -> u64 {
+    std::env::var("FORETELL_IMAGE_CACHE_BYTES")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_IMAGE_CACHE_BUDGET_BYTES)
+}
+
+/// Lets a front end surface progress and errors from background cache work
+/// without this crate depending on any particular notification mechanism.
+pub trait Notifier: Send + Sync {
+    /// A set's card list finished downloading.
+    fn set_added(&self, set_name: &str, set_code: &str, card_count: usize);
+    /// Background cache work failed; `context` describes what was attempted.
+    fn error(&self, context: &str, err: &anyhow::Error);
+    /// A low-stakes status line about background cache work (a refresh
+    /// starting, a set being re-fetched). Unlike [`Notifier::error`], nothing
+    /// went wrong; a front end embedding this crate is free to drop these.
+    fn progress(&self, message: &str);
+}
+
+/// A [`Notifier`] that drops every event, for front ends that don't care.
+pub struct NoopNotifier;
+
+impl Notifier for NoopNotifier {
+    fn set_added(&self, _set_name: &str, _set_code: &str, _card_count: usize) {}
+    fn error(&self, _context: &str, _err: &anyhow::Error) {}
+    fn progress(&self, _message: &str) {}
+}
+
+struct Inner {
+    dir: PathBuf,
+    client: reqwest::Client,
+    notifier: Arc<dyn Notifier>,
+    background: Mutex<Option<JoinHandle<()>>>,
+}
+
+/// Handle to the Scryfall set/card list cache and the content-addressed
+/// image cache backing it. Cheap to clone: internally reference-counted.
+#[derive(Clone)]
+pub struct CardCache(Arc<Inner>);
+
+impl CardCache {
+    /// Opens (creating if necessary) a cache rooted at `dir`, reporting
+    /// background progress and errors through `notifier`.
+    pub async fn open(dir: impl Into<PathBuf>, notifier: Arc<dyn Notifier>) -> anyhow::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).await.context("creating cache dir")?;
+        Ok(Self(Arc::new(Inner {
+            dir,
+            client: reqwest::Client::new(),
+            notifier,
+            background: Mutex::new(None),
+        })))
+    }
+
+    /// Directory holding one `<code>.cards` and `<code>.meta` file pair per
+    /// set, so a single set can be invalidated and re-fetched without
+    /// touching the rest of the cache.
+    fn sets_dir(&self) -> PathBuf {
+        self.0.dir.join("sets")
+    }
+
+    fn lock_file(&self) -> PathBuf {
+        self.0.dir.join("lock")
+    }
+
+    fn images_dir(&self) -> PathBuf {
+        self.0.dir.join("images")
+    }
+
+    /// Returns the merged card-name list assembled from the per-set cache
+    /// files, kicking off a background refresh (guarded by a lock file so
+    /// only one process does it at a time) if one isn't already running.
+    ///
+    /// The snapshot is read before the refresh is spawned, not after, so
+    /// this call never races the background task's rewrite of a stale set's
+    /// `.cards` file within this process.
+    pub async fn card_names(&self) -> anyhow::Result<Vec<String>> {
+        self.0.notifier.progress("getting missing sets");
+        let names = self.merged_card_names().await?;
+
+        let lock_file = self.lock_file();
+        let _ = OpenOptions::new()
+            .create_new(true)
+            .write(true)
+            .open(&lock_file)
+            .await;
+        match fmutex::try_lock(&lock_file) {
+            Ok(Some(guard)) => {
+                let this = self.clone();
+                let handle = tokio::spawn(async move {
+                    if let Err(e) = this.refresh_missing_sets().await {
+                        this.0.notifier.error("refreshing missing sets", &e);
+                    }
+                    if let Err(e) = this.prune_images(image_cache_budget_bytes()).await {
+                        this.0.notifier.error("pruning image cache", &e);
+                    }
+                    drop(guard);
+                });
+                *self.0.background.lock().await = Some(handle);
+            }
+            Ok(None) => {}
+            Err(e) => self.0.notifier.error(
+                "locking cache",
+                &anyhow::anyhow!("failed to lock {lock_file:?}: {e}"),
+            ),
+        }
+
+        Ok(names)
+    }
+
+    /// Streams every `<code>.cards` file under the sets cache and merges
+    /// them into one deduplicated list, in set-code order.
+    async fn merged_card_names(&self) -> anyhow::Result<Vec<String>> {
+        let sets_dir = self.sets_dir();
+        let mut dir = match fs::read_dir(&sets_dir).await {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut card_files = Vec::new();
+        while let Some(entry) = dir.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) == Some("cards") {
+                card_files.push(path);
+            }
+        }
+        card_files.sort();
+
+        let mut seen = HashSet::new();
+        let mut names = Vec::new();
+        for path in card_files {
+            let file = File::open(&path).await?;
+            let mut lines = BufReader::new(file).lines();
+            while let Some(name) = lines.next_line().await? {
+                if seen.insert(name.clone()) {
+                    names.push(name);
+                }
+            }
+        }
+        Ok(names)
+    }
+
+    /// Awaits any in-flight background refresh spawned by [`CardCache::card_names`].
+    pub async fn join_background(&self) -> anyhow::Result<()> {
+        let handle = self.0.background.lock().await.take();
+        if let Some(handle) = handle {
+            handle
+                .await
+                .map_err(|e| anyhow::anyhow!("background update task panicked: {e:?}"))?;
+        }
+        Ok(())
+    }
+
+    /// Re-pulls every set whose card list looks stale: never fetched, a
+    /// previous fetch found no cards yet (the set was announced before its
+    /// cards were), or Scryfall's card count for the set no longer matches
+    /// what we last stored (errata, reprints, or late additions landed).
+    ///
+    /// `scryfall::Set` has no "last updated" timestamp to key staleness off
+    /// of, so `card_count` is used as a proxy instead; this is a deliberate
+    /// deviation, and it means a set whose cards changed without its total
+    /// count changing (pure errata to existing cards, for example) won't be
+    /// picked up by this check.
+    pub async fn refresh_missing_sets(&self) -> anyhow::Result<()> {
+        let sets_dir = self.sets_dir();
+        fs::create_dir_all(&sets_dir)
+            .await
+            .context("creating sets cache dir")?;
+        let notifier = &self.0.notifier;
+        let date_threashold = new_set_threashold();
+        Set::all()
+            .await?
+            .into_stream()
+            .filter_map(|o| future::ready(o.ok()))
+            .filter(|s| {
+                future::ready(
+                    [
+                        SetType::Memorabilia,
+                        SetType::Token,
+                        SetType::Alchemy,
+                        SetType::TreasureChest,
+                        SetType::Promo,
+                    ]
+                    .into_iter()
+                    .all(|t| s.set_type != t),
+                )
+            })
+            .filter(|s| future::ready(!s.digital))
+            .filter(move |s| future::ready(matches!(s.released_at, Some(d) if d <= date_threashold)))
+            .map(|set| {
+                let sets_dir = &sets_dir;
+                async move { refresh_set(sets_dir, set, notifier).await }
+            })
+            .buffer_unordered(available_parallelism().unwrap().get())
+            .for_each(|result| async move {
+                if let Err(e) = result {
+                    notifier.error("updating a set's card list", &e);
+                }
+            })
+            .await;
+        Ok(())
+    }
+
+    /// Evicts least-recently-used entries from the image cache once its
+    /// total size exceeds `budget_bytes`.
+    pub async fn prune_images(&self, budget_bytes: u64) -> anyhow::Result<()> {
+        let images_dir = self.images_dir();
+        let mut entries = Vec::new();
+        let mut total = 0u64;
+        walk_image_cache(&images_dir, |path, mtime, size| {
+            total += size;
+            entries.push((path, mtime, size));
+        })
+        .await?;
+        if total <= budget_bytes {
+            return Ok(());
+        }
+        entries.sort_by_key(|(_, mtime, _)| *mtime);
+        for (path, _, size) in entries {
+            if total <= budget_bytes {
+                break;
+            }
+            fs::remove_file(&path)
+                .await
+                .with_context(|| format!("evicting {path:?} from image cache"))?;
+            total -= size;
+        }
+        Ok(())
+    }
+
+    /// Returns the cache path an image URI would live at, without touching
+    /// the filesystem.
+    pub fn cached_image_path(&self, uri: &str) -> PathBuf {
+        image_cache_path(&self.images_dir(), uri)
+    }
+
+    /// Hands back the cached path for `uri`, downloading and persisting it
+    /// into the cache first on a miss. Touches the file's mtime on a hit, so
+    /// [`CardCache::prune_images`]'s LRU eviction tracks last use rather than
+    /// just first download.
+    pub async fn fetch_image(&self, uri: &str) -> anyhow::Result<PathBuf> {
+        let target = self.cached_image_path(uri);
+        if fs::try_exists(&target).await.unwrap_or(false) {
+            let now = filetime::FileTime::now();
+            let _ = filetime::set_file_mtime(&target, now);
+            return Ok(target);
+        }
+        download_card_image(&self.0.client, uri, &target).await
+    }
+
+    /// Runs `search_query` against Scryfall and returns the large-art URIs
+    /// of every matching printing.
+    pub async fn query_image_uris(&self, search_query: &str) -> anyhow::Result<Vec<String>> {
+        let notifier = &self.0.notifier;
+        Card::search(search_query)
+            .await?
+            .into_stream()
+            .map(|c| {
+                c.map(|mut c| {
+                    let uris = card_image_uris(&mut c);
+                    if uris.is_empty() {
+                        notifier.error(
+                            "extracting image uris",
+                            &anyhow::anyhow!("failed to get any uris for card {}", c.name),
+                        );
+                    }
+                    uris
+                })
+            })
+            .try_fold(Vec::new(), |mut acc, v| async move {
+                acc.extend(v);
+                Ok::<_, Error>(acc)
+            })
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Looks `name` up with fuzzy matching and returns its first printing's
+    /// large-art URI, if any.
+    pub async fn named_image_uri(&self, name: &str) -> anyhow::Result<Option<String>> {
+        let mut card = Card::named_fuzzy(name).await?;
+        Ok(card_image_uris(&mut card).into_iter().next())
+    }
+}
+
+fn new_set_threashold() -> NaiveDate {
+    chrono::Utc::now().naive_utc().date() + Duration::weeks(1)
+}
+
+/// What we knew about a set's card list as of its last successful (or
+/// unsuccessful) fetch, stored alongside its `.cards` file so a later run
+/// can tell whether it's gone stale without re-downloading anything.
+struct SetMeta {
+    last_fetched: NaiveDate,
+    upstream_card_count: usize,
+    fetched_ok: bool,
+}
+
+impl SetMeta {
+    fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\n",
+            self.last_fetched,
+            self.upstream_card_count,
+            self.fetched_ok as u8
+        )
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        let last_fetched = fields.next()?.parse().ok()?;
+        let upstream_card_count = fields.next()?.parse().ok()?;
+        let fetched_ok = fields.next()?.trim() == "1";
+        Some(Self {
+            last_fetched,
+            upstream_card_count,
+            fetched_ok,
+        })
+    }
+}
+
+async fn read_set_meta(path: &Path) -> anyhow::Result<Option<SetMeta>> {
+    match fs::read_to_string(path).await {
+        Ok(contents) => Ok(SetMeta::from_line(&contents)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn write_set_meta(path: &Path, meta: &SetMeta) -> anyhow::Result<()> {
+    fs::write(path, meta.to_line())
+        .await
+        .with_context(|| format!("writing set meta at {path:?}"))
+}
+
+/// Refreshes one set's `<code>.cards`/`<code>.meta` pair if its meta says
+/// it's stale, leaving it untouched otherwise.
+async fn refresh_set(sets_dir: &Path, set: Set, notifier: &Arc<dyn Notifier>) -> anyhow::Result<()> {
+    let Set {
+        code,
+        name,
+        set_type,
+        card_count,
+        ..
+    } = set;
+    let cards_path = sets_dir.join(format!("{code}.cards"));
+    let meta_path = sets_dir.join(format!("{code}.meta"));
+
+    let meta = read_set_meta(&meta_path).await?;
+    let needs_refresh = match &meta {
+        None => true,
+        Some(meta) => !meta.fetched_ok || meta.upstream_card_count != card_count,
+    };
+    if !needs_refresh {
+        return Ok(());
+    }
+
+    notifier.progress(&format!("updating card list for {name} ({code}) :: {set_type}"));
+    let fetched = update_set_card_list(&cards_path, code, &name, notifier)
+        .await
+        .with_context(|| format!("updating card list for set {name} ({code})"))?;
+    write_set_meta(
+        &meta_path,
+        &SetMeta {
+            last_fetched: chrono::Utc::now().naive_utc().date(),
+            upstream_card_count: card_count,
+            fetched_ok: fetched.is_some(),
+        },
+    )
+    .await?;
+    if let Some(count) = fetched {
+        notifier.set_added(&name, code.get(), count);
+    }
+    Ok(())
+}
+
+/// Overwrites `path` with the set's current card list.
+///
+/// Writes to a sibling temp file and renames it into place atomically
+/// (mirroring [`download_card_image`]), so a reader racing this write
+/// (another process, or this one's own in-process read taken right before
+/// spawning a refresh) never observes a truncated or partial card list.
+///
+/// If no cards were found for the set, returns `Ok(None)`; this happens
+/// when new sets are added before their cards are added.
+async fn update_set_card_list(
+    path: &Path,
+    set_code: SetCode,
+    set_name: &str,
+    notifier: &Arc<dyn Notifier>,
+) -> anyhow::Result<Option<usize>> {
+    const JUST_DONT: &str = "Our Market Research Shows That Players Like Really Long Card Names So We Made this Card to Have the Absolute Longest Card Name Ever Elemental";
+    use scryfall::search::prelude::*;
+    let parent = path.parent().expect("cards path always has a parent");
+    let (tmp_file, tmp_path) = NamedTempFile::new_in(parent)
+        .with_context(|| format!("creating temp file in {parent:?}"))?
+        .into_parts();
+    let mut file = BufWriter::new(File::from_std(tmp_file));
+
+    let count = match Card::search(set(set_code).and(game(Game::Paper))).await {
+        Ok(cards) => {
+            let file = &mut file;
+            let card_names = cards
+                .into_stream()
+                .filter_map(|o| future::ready(o.ok()))
+                .filter(|c| -> future::Ready<bool> {
+                    future::ready(
+                        c.type_line.as_deref() != Some("Basic")
+                            && c.type_line.as_deref() != Some("Token"),
+                    )
+                })
+                .map(|c| c.name)
+                .filter(|n| future::ready(n != JUST_DONT));
+            tokio::pin!(card_names);
+            let mut count = 0;
+            while let Some(name) = card_names.next().await {
+                file.write_all(name.as_bytes()).await?;
+                file.write_all(b"\n").await?;
+                count += 1;
+            }
+            file.flush().await?;
+            count
+        }
+        Err(Error::ScryfallError(e @ ScryfallError { status: 404, .. })) => {
+            notifier.progress(&format!(
+                "got a 404 downloading set {set_name} ({set_code}): {e:#?}"
+            ));
+            return Ok(None);
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    tmp_path
+        .persist(path)
+        .with_context(|| format!("persisting card list to {path:?}"))?;
+    Ok(Some(count))
+}
+
+/// Pulls the large-size art URI(s) off a card, checking both single- and
+/// multi-faced layouts.
+fn card_image_uris(c: &mut Card) -> Vec<String> {
+    if let Some(large) = c.image_uris.remove("large") {
+        vec![large.to_string()]
+    } else if let Some(faces) = c.card_faces.take() {
+        faces
+            .into_iter()
+            .filter_map(|face| face.image_uris.and_then(|mut u| u.remove("large")))
+            .map(|uri| uri.to_string())
+            .collect::<Vec<_>>()
+    } else {
+        vec![]
+    }
+}
+
+/// Maps an image URI to its content-addressed location on disk, fanned out
+/// two levels deep (`ab/cdef...`) so the cache never holds one huge flat
+/// directory.
+fn image_cache_path(images_dir: &Path, uri: &str) -> PathBuf {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    uri.hash(&mut hasher);
+    let hash = format!("{:016x}", hasher.finish());
+    images_dir.join(&hash[..2]).join(&hash[2..])
+}
+
+/// Walks the two-level fan-out image cache, invoking `visit` with the path,
+/// mtime and size of every cached file.
+async fn walk_image_cache<F>(images_dir: &Path, mut visit: F) -> anyhow::Result<()>
+where
+    F: FnMut(PathBuf, SystemTime, u64),
+{
+    let mut shards = match fs::read_dir(images_dir).await {
+        Ok(dir) => dir,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    while let Some(shard) = shards.next_entry().await? {
+        if !shard.file_type().await?.is_dir() {
+            continue;
+        }
+        let mut entries = fs::read_dir(shard.path()).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let meta = entry.metadata().await?;
+            if meta.is_file() {
+                visit(entry.path(), meta.modified()?, meta.len());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Downloads `uri` into the image cache at `target`, writing to a sibling
+/// temp file first and atomically renaming it into place so a crash mid
+/// download never leaves a partial file under its final name.
+async fn download_card_image(
+    client: &reqwest::Client,
+    uri: &str,
+    target: &Path,
+) -> anyhow::Result<PathBuf> {
+    let parent = target.parent().expect("cache path always has a parent");
+    fs::create_dir_all(parent)
+        .await
+        .with_context(|| format!("creating image cache dir {parent:?}"))?;
+    let (file, tmp_path) = NamedTempFile::new_in(parent)?.into_parts();
+    let mut file = File::from_std(file);
+    let mut bytes = client
+        .get(uri)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes_stream();
+    while let Some(b) = bytes.next().await {
+        file.write_all(&b?).await?;
+    }
+    file.flush().await?;
+    if let Err(e) = tmp_path.persist(target) {
+        if !target.exists() {
+            return Err(e.error.into());
+        }
+    }
+    Ok(target.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_cache_path_fans_out_two_levels_and_is_deterministic() {
+        let dir = Path::new("/cache/images");
+        let a = image_cache_path(dir, "https://example.com/a.jpg");
+        let b = image_cache_path(dir, "https://example.com/b.jpg");
+
+        assert_eq!(image_cache_path(dir, "https://example.com/a.jpg"), a);
+        assert_ne!(a, b);
+
+        let shard = a.parent().unwrap();
+        assert_eq!(shard.parent().unwrap(), dir);
+        assert_eq!(shard.file_name().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn prune_images_evicts_oldest_first_until_under_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = CardCache::open(dir.path(), Arc::new(NoopNotifier))
+            .await
+            .unwrap();
+        let shard = dir.path().join("images").join("ab");
+        fs::create_dir_all(&shard).await.unwrap();
+
+        // Three 10-byte entries, oldest to newest.
+        for (name, age_secs) in [("oldest", 30), ("middle", 20), ("newest", 10)] {
+            let path = shard.join(name);
+            fs::write(&path, b"0123456789").await.unwrap();
+            let mtime = filetime::FileTime::from_unix_time(
+                filetime::FileTime::now().seconds() - age_secs,
+                0,
+            );
+            filetime::set_file_mtime(&path, mtime).unwrap();
+        }
+
+        // Budget only has room for two of the three 10-byte entries.
+        cache.prune_images(20).await.unwrap();
+
+        assert!(!fs::try_exists(shard.join("oldest")).await.unwrap());
+        assert!(fs::try_exists(shard.join("middle")).await.unwrap());
+        assert!(fs::try_exists(shard.join("newest")).await.unwrap());
+    }
+
+    #[test]
+    fn set_meta_round_trips_through_a_line() {
+        let meta = SetMeta {
+            last_fetched: NaiveDate::from_ymd_opt(2026, 7, 29).unwrap(),
+            upstream_card_count: 287,
+            fetched_ok: true,
+        };
+        let parsed = SetMeta::from_line(&meta.to_line()).unwrap();
+        assert_eq!(parsed.last_fetched, meta.last_fetched);
+        assert_eq!(parsed.upstream_card_count, meta.upstream_card_count);
+        assert_eq!(parsed.fetched_ok, meta.fetched_ok);
+    }
+
+    #[test]
+    fn set_meta_from_line_never_reads_garbage_as_ok_and_rejects_truncated_lines() {
+        // Anything other than a literal "1" for fetched_ok reads as false,
+        // never panics or silently succeeds as true.
+        let meta = SetMeta::from_line("2026-07-29\t287\t9\n").unwrap();
+        assert!(!meta.fetched_ok);
+
+        // A truncated line (missing the fetched_ok field) fails to parse
+        // rather than defaulting to something that looks plausible.
+        assert!(SetMeta::from_line("2026-07-29\t287").is_none());
+        assert!(SetMeta::from_line("").is_none());
+    }
+}
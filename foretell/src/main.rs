@@ -0,0 +1,266 @@
+use foretell_core::{CardCache, Notifier};
+use futures_util::stream::{self, StreamExt};
+use notify_rust::{Notification, NotificationHandle, Urgency};
+use std::{
+    fmt::Display, os::unix::process::ExitStatusExt, path::PathBuf, process::Stdio, sync::Arc,
+    thread::available_parallelism,
+};
+use tokio::{
+    io::{self, AsyncWriteExt, BufWriter},
+    process::Command,
+    sync::{Mutex, Semaphore},
+    task,
+};
+
+mod tui;
+
+fn notify<T, B>(title: T, body: B) -> Option<NotificationHandle>
+where
+    T: Display,
+    B: Display,
+{
+    let summary = format!("{title}");
+    let body = format!("{body}");
+    let e = Notification::new()
+        .summary(&summary)
+        .body(&body)
+        .urgency(Urgency::Low)
+        .show();
+    match e {
+        Ok(h) => Some(h),
+        Err(e) => {
+            println!("failed to notify: {e}");
+            backup_notify(&summary, &body, "low");
+            None
+        }
+    }
+}
+
+fn error(e: anyhow::Error) {
+    let summary = "Error foretelling";
+    let body = format!("{e:?}");
+    let e = Notification::new()
+        .summary(summary)
+        .body(&body)
+        .urgency(Urgency::Critical)
+        .show();
+    if let Err(e) = e {
+        println!("failed to notify error: {e}");
+        backup_notify(summary, &body, "critical");
+    }
+}
+
+fn backup_notify(summary: &str, body: &str, urgency: &str) {
+    let child = Command::new("notify-send")
+        .args([summary, body, "-u", urgency])
+        .spawn();
+    if let Ok(mut child) = child {
+        task::spawn(async move {
+            let _ = child.wait().await;
+        });
+    }
+}
+
+/// Wires [`foretell_core`]'s background progress/error events to desktop
+/// notifications.
+struct DesktopNotifier;
+
+impl Notifier for DesktopNotifier {
+    fn set_added(&self, set_name: &str, set_code: &str, card_count: usize) {
+        notify(
+            format!("Set {set_name} ({set_code}) added!"),
+            format!("{card_count} cards in its list"),
+        );
+    }
+
+    fn error(&self, context: &str, err: &anyhow::Error) {
+        error(anyhow::anyhow!("{err:?}").context(context.to_string()));
+    }
+
+    fn progress(&self, message: &str) {
+        println!("{message}");
+    }
+}
+
+async fn query(cache: &CardCache) -> anyhow::Result<String> {
+    let names = match cache.card_names().await {
+        Ok(names) => names,
+        Err(e) => {
+            error(e);
+            Vec::new()
+        }
+    };
+    if tui::enabled() {
+        query_tui(cache, names).await
+    } else {
+        query_dmenu(names).await
+    }
+}
+
+/// Reads the selected card's first printing and caches its art, so the TUI
+/// can show a preview as the user browses the list.
+async fn preview_image(cache: &CardCache, name: &str) -> Option<PathBuf> {
+    let uri = cache.named_image_uri(name).await.ok().flatten()?;
+    cache.fetch_image(&uri).await.ok()
+}
+
+async fn query_tui(cache: &CardCache, names: Vec<String>) -> anyhow::Result<String> {
+    let cache = cache.clone();
+    tui::pick(names, move |name| {
+        let cache = cache.clone();
+        Box::pin(async move { preview_image(&cache, &name).await })
+    })
+    .await
+}
+
+async fn query_dmenu(names: Vec<String>) -> anyhow::Result<String> {
+    let mut dmenu = Command::new("dmenu")
+        .args(["-p", "scry", "-l", "30", "-i"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    {
+        let mut pipe = BufWriter::new(dmenu.stdin.take().expect("stdin was piped"));
+        for name in &names {
+            pipe.write_all(name.as_bytes()).await?;
+            pipe.write_all(b"\n").await?;
+        }
+    }
+    let output = dmenu.wait_with_output().await?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().into())
+    } else if output.status.core_dumped() {
+        Err(anyhow::anyhow!("core dumped :("))
+    } else if let Some(sig) = output.status.signal() {
+        Err(anyhow::anyhow!("killed by signal: {sig}"))
+    } else {
+        Err(anyhow::anyhow!(
+            "process exited with status: {:?}",
+            output.status.code()
+        ))
+    }
+}
+
+struct ProgressNotifier {
+    total: usize,
+    current: usize,
+    last_notif: usize,
+    notification_handle: Option<NotificationHandle>,
+}
+
+impl ProgressNotifier {
+    fn new(total: usize) -> Self {
+        let mut this = Self {
+            total,
+            current: 0,
+            last_notif: 0,
+            notification_handle: None,
+        };
+        this.notify();
+        this
+    }
+
+    fn progress(&mut self) {
+        self.current += 1;
+        self.notify();
+    }
+
+    fn notify(&mut self) {
+        if (self.current * 10 / self.total) == self.last_notif {
+            let body = format!("{}/{} done", self.current, self.total);
+            match self.notification_handle.as_mut() {
+                Some(handle) => {
+                    handle.body(&body);
+                    let _ = handle.update();
+                }
+                None => {
+                    self.notification_handle = notify("Downloading", body);
+                }
+            }
+            self.last_notif += 1;
+        }
+    }
+}
+
+async fn run(cache: &CardCache) -> anyhow::Result<()> {
+    let query = query(cache).await?;
+    if query.is_empty() {
+        return Ok(());
+    }
+    let uris = cache.query_image_uris(&query).await?;
+    if uris.is_empty() {
+        return Err(anyhow::anyhow!("no cards found"));
+    }
+
+    let progress = Arc::new(Mutex::new(ProgressNotifier::new(uris.len())));
+    let semaphore = Arc::new(Semaphore::new(available_parallelism()?.get()));
+    let mut slots: Vec<Option<PathBuf>> = (0..uris.len()).map(|_| None).collect();
+    let mut downloads = stream::iter(uris.into_iter().enumerate())
+        .map(|(i, uri)| {
+            let cache = cache.clone();
+            let semaphore = Arc::clone(&semaphore);
+            let progress = Arc::clone(&progress);
+            async move {
+                let permit = semaphore.acquire().await.expect("semaphore never closed");
+                let result = cache.fetch_image(&uri).await;
+                drop(permit);
+                progress.lock().await.progress();
+                (i, uri, result)
+            }
+        })
+        .buffer_unordered(available_parallelism()?.get());
+    while let Some((i, uri, result)) = downloads.next().await {
+        match result {
+            Ok(path) => slots[i] = Some(path),
+            Err(e) => error(e.context(format!("downloading image from {uri}"))),
+        }
+    }
+    let files: Vec<PathBuf> = slots.into_iter().flatten().collect();
+
+    for binary in ["sxiv", "nsxiv", "xdg-open"] {
+        let mut cmd = Command::new(binary);
+        if binary.contains("sxiv") {
+            cmd.args(["-b", "-g", "590x800"]);
+        }
+        let mut process = match cmd.args(files.iter()).spawn() {
+            Ok(process) => process,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let status = process.wait().await?;
+        return if !status.success() {
+            Err(anyhow::anyhow!("image viewer error {status}"))
+        } else {
+            Ok(())
+        };
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    let cache_dir = match dirs::cache_dir().ok_or_else(|| anyhow::anyhow!("can't find cache dir")) {
+        Ok(dir) => dir.join("foretell"),
+        Err(e) => {
+            error(e);
+            return;
+        }
+    };
+    let cache = match CardCache::open(cache_dir, Arc::new(DesktopNotifier)).await {
+        Ok(cache) => cache,
+        Err(e) => {
+            error(e);
+            return;
+        }
+    };
+
+    if let Err(e) = run(&cache).await {
+        error(e)
+    }
+    if let Err(e) = cache.join_background().await {
+        error(e);
+    } else {
+        println!("background task ended");
+    }
+}
@@ -0,0 +1,257 @@
+//! Built-in terminal UI for picking a card, used instead of dmenu when
+//! running in a real terminal. Filters the card list as the user types and
+//! renders a live preview of the highlighted card using half-block cells, so
+//! picking a card needs no external binary. Once a card is picked, its
+//! printings' art is still handed to an external image viewer (sxiv, nsxiv
+//! or xdg-open), the same as after a dmenu pick.
+
+use crossterm::{
+    event::{Event, EventStream, KeyCode, KeyEventKind},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use futures_util::StreamExt;
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Terminal,
+};
+use std::{
+    future::Future,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    pin::Pin,
+};
+use tokio::sync::mpsc;
+
+type PreviewFuture = Pin<Box<dyn Future<Output = Option<PathBuf>> + Send>>;
+
+/// Whether the TUI picker should be used: only when stdout is a real
+/// terminal, and the user hasn't opted out with `FORETELL_NO_TUI`.
+pub fn enabled() -> bool {
+    std::env::var_os("FORETELL_NO_TUI").is_none() && io::stdout().is_terminal()
+}
+
+/// Runs the picker over `names`, calling `fetch_preview` whenever the
+/// highlighted entry changes to fetch (and cache) its art. Returns the
+/// selected name, or an empty string if the user cancelled.
+pub async fn pick(
+    names: Vec<String>,
+    fetch_preview: impl FnMut(String) -> PreviewFuture,
+) -> anyhow::Result<String> {
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+
+    let result = run(&mut terminal, names, fetch_preview).await;
+
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+
+    result
+}
+
+struct State {
+    filter: String,
+    matches: Vec<usize>,
+    list_state: ListState,
+    preview: Option<Vec<Line<'static>>>,
+    previewed: Option<usize>,
+}
+
+async fn run(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    names: Vec<String>,
+    mut fetch_preview: impl FnMut(String) -> PreviewFuture,
+) -> anyhow::Result<String> {
+    let mut state = State {
+        matches: (0..names.len()).collect(),
+        filter: String::new(),
+        list_state: ListState::default().with_selected(Some(0)),
+        preview: None,
+        previewed: None,
+    };
+    let mut events = EventStream::new();
+    let (preview_tx, mut preview_rx) = mpsc::unbounded_channel();
+    let mut preview_generation = 0u64;
+
+    loop {
+        let selected = state.list_state.selected().and_then(|i| state.matches.get(i).copied());
+        if selected != state.previewed {
+            state.previewed = selected;
+            preview_generation += 1;
+            match selected {
+                Some(idx) => {
+                    let generation = preview_generation;
+                    let fetch = fetch_preview(names[idx].clone());
+                    let tx = preview_tx.clone();
+                    tokio::spawn(async move {
+                        let preview = fetch.await.and_then(|path| decode_preview(&path, 48, 24).ok());
+                        let _ = tx.send((generation, preview));
+                    });
+                }
+                None => state.preview = None,
+            }
+        }
+
+        terminal.draw(|frame| draw(frame, &names, &state))?;
+
+        tokio::select! {
+            Some((generation, preview)) = preview_rx.recv() => {
+                if generation == preview_generation {
+                    state.preview = preview;
+                }
+            }
+            event = events.next() => match event {
+                Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Esc => return Ok(String::new()),
+                    KeyCode::Enter => {
+                        return Ok(state
+                            .list_state
+                            .selected()
+                            .and_then(|i| state.matches.get(i))
+                            .map(|&idx| names[idx].clone())
+                            .unwrap_or_default())
+                    }
+                    KeyCode::Up => select(&mut state, -1),
+                    KeyCode::Down => select(&mut state, 1),
+                    KeyCode::Backspace => {
+                        state.filter.pop();
+                        refilter(&mut state, &names);
+                    }
+                    KeyCode::Char(c) => {
+                        state.filter.push(c);
+                        refilter(&mut state, &names);
+                    }
+                    _ => {}
+                },
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e.into()),
+                None => return Ok(String::new()),
+            },
+        }
+    }
+}
+
+fn select(state: &mut State, delta: i64) {
+    if state.matches.is_empty() {
+        state.list_state.select(None);
+        return;
+    }
+    let current = state.list_state.selected().unwrap_or(0) as i64;
+    let next = (current + delta).clamp(0, state.matches.len() as i64 - 1);
+    state.list_state.select(Some(next as usize));
+}
+
+fn refilter(state: &mut State, names: &[String]) {
+    state.matches = matching_indices(names, &state.filter);
+    state.list_state.select(if state.matches.is_empty() {
+        None
+    } else {
+        Some(0)
+    });
+}
+
+fn matching_indices(names: &[String], filter: &str) -> Vec<usize> {
+    let mut scored: Vec<(usize, i64)> = names
+        .iter()
+        .enumerate()
+        .filter_map(|(i, name)| fuzzy_score(name, filter).map(|score| (i, score)))
+        .collect();
+    scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+    scored.into_iter().map(|(i, _)| i).collect()
+}
+
+/// Scores `candidate` against `pattern` as a case-insensitive subsequence
+/// match, rewarding consecutive runs and shorter candidates. Returns `None`
+/// when `pattern` isn't a subsequence of `candidate` at all.
+fn fuzzy_score(candidate: &str, pattern: &str) -> Option<i64> {
+    if pattern.is_empty() {
+        return Some(0);
+    }
+    let mut wanted = pattern.chars().flat_map(char::to_lowercase).peekable();
+    let mut score = 0i64;
+    let mut last_match: Option<usize> = None;
+    for (i, c) in candidate.chars().flat_map(char::to_lowercase).enumerate() {
+        let Some(&want) = wanted.peek() else { break };
+        if c == want {
+            score += 1;
+            if last_match == Some(i.wrapping_sub(1)) {
+                score += 2;
+            }
+            last_match = Some(i);
+            wanted.next();
+        }
+    }
+    if wanted.peek().is_some() {
+        None
+    } else {
+        Some(score - candidate.chars().count() as i64 / 32)
+    }
+}
+
+/// Decodes the image at `path` and downsamples it into `width`x`height`
+/// terminal cells, packing two source rows into each cell via the upper
+/// half-block character so both the foreground and background color of a
+/// cell carry real pixel data.
+fn decode_preview(path: &Path, width: u16, height: u16) -> anyhow::Result<Vec<Line<'static>>> {
+    let img = image::open(path)?.into_rgb8();
+    let img = image::imageops::resize(
+        &img,
+        width as u32,
+        height as u32 * 2,
+        image::imageops::FilterType::Triangle,
+    );
+    let lines = (0..height)
+        .map(|row| {
+            let spans = (0..width)
+                .map(|col| {
+                    let top = img.get_pixel(col as u32, row as u32 * 2);
+                    let bottom = img.get_pixel(col as u32, row as u32 * 2 + 1);
+                    Span::styled(
+                        "\u{2580}",
+                        Style::default()
+                            .fg(Color::Rgb(top[0], top[1], top[2]))
+                            .bg(Color::Rgb(bottom[0], bottom[1], bottom[2])),
+                    )
+                })
+                .collect::<Vec<_>>();
+            Line::from(spans)
+        })
+        .collect();
+    Ok(lines)
+}
+
+fn draw(frame: &mut ratatui::Frame, names: &[String], state: &State) {
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(55), Constraint::Percentage(45)])
+        .split(frame.size());
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(3)])
+        .split(columns[0]);
+
+    let items: Vec<ListItem> = state
+        .matches
+        .iter()
+        .map(|&i| ListItem::new(names[i].as_str()))
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("scry"))
+        .highlight_style(Style::default().bg(Color::DarkGray));
+    frame.render_stateful_widget(list, rows[0], &mut state.list_state.clone());
+
+    let filter = Paragraph::new(state.filter.as_str())
+        .block(Block::default().borders(Borders::ALL).title("filter"));
+    frame.render_widget(filter, rows[1]);
+
+    let preview = Paragraph::new(state.preview.clone().unwrap_or_default())
+        .block(Block::default().borders(Borders::ALL).title("preview"));
+    frame.render_widget(preview, columns[1]);
+}